@@ -0,0 +1,31 @@
+//! Single-flight guard for session re-authentication: when the upstream
+//! starts rejecting our cookie, many concurrent proxied requests can notice
+//! at once. Without this, each one would kick off its own
+//! sign→verify→make-message→login round trip to `sign:sign:sys`. This
+//! serializes that into (at most) one in-flight re-auth at a time.
+
+#[derive(Default)]
+pub struct ReauthGuard {
+    in_progress: bool,
+}
+
+impl ReauthGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim the right to re-authenticate. Returns `false` if another
+    /// request already has the lock, meaning the caller should just forward
+    /// whatever failure it saw instead of piling on more login attempts.
+    pub fn try_acquire(&mut self) -> bool {
+        if self.in_progress {
+            return false;
+        }
+        self.in_progress = true;
+        true
+    }
+
+    pub fn release(&mut self) {
+        self.in_progress = false;
+    }
+}
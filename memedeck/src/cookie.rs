@@ -0,0 +1,373 @@
+//! A minimal RFC 6265 cookie jar: enough to track the cookies a web2 backend
+//! sets across a session without forcing callers to hand-parse `Set-Cookie`
+//! headers or babysit a single opaque string.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Unix timestamp (seconds) after which this cookie is no longer valid.
+    /// `None` means session-only: still sent, never expired by us.
+    pub expires_at: Option<u64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    /// Set when the `Set-Cookie` header had no `Domain` attribute. Per RFC
+    /// 6265 §5.3, a host-only cookie is sent only to the exact host that set
+    /// it, never to subdomains -- unlike a cookie with an explicit `Domain`.
+    pub host_only: bool,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires_at, Some(exp) if exp <= now)
+    }
+
+    fn matches(&self, host: &str, path: &str) -> bool {
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain || host.ends_with(&format!(".{}", self.domain))
+        };
+        let path_matches = path == self.path || path.starts_with(&format!("{}/", self.path.trim_end_matches('/')));
+        domain_matches && path_matches
+    }
+}
+
+/// A cookie jar keyed by `(domain, path, name)`, mirroring how browsers key
+/// their own stores so that two cookies with the same name on different
+/// paths/domains don't clobber each other.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CookieJar {
+    entries: HashMap<(String, String, String), Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse every `Set-Cookie` header value from an upstream response and
+    /// store each resulting cookie, defaulting `Domain`/`Path` from the
+    /// request URL when the header omits them (per RFC 6265 §5.2).
+    pub fn absorb_set_cookie_headers<'a, I: IntoIterator<Item = &'a str>>(
+        &mut self,
+        headers: I,
+        request_url: &Url,
+    ) {
+        for header in headers {
+            if let Some(cookie) = parse_set_cookie(header, request_url) {
+                let key = (cookie.domain.clone(), cookie.path.clone(), cookie.name.clone());
+                self.entries.insert(key, cookie);
+            }
+        }
+    }
+
+    /// Insert a single already-built cookie, e.g. the login session token
+    /// minted outside of a `Set-Cookie` header.
+    pub fn store(&mut self, cookie: Cookie) {
+        let key = (cookie.domain.clone(), cookie.path.clone(), cookie.name.clone());
+        self.entries.insert(key, cookie);
+    }
+
+    /// Drop every entry that has expired as of `now`.
+    pub fn purge_expired(&mut self, now: u64) {
+        self.entries.retain(|_, c| !c.is_expired(now));
+    }
+
+    /// Build the `Cookie:` request header value for `url`, selecting every
+    /// non-expired entry whose domain and path match. Returns `None` when no
+    /// cookie applies.
+    pub fn cookie_header_for(&self, url: &Url, now: u64) -> Option<String> {
+        let host = url.host_str().unwrap_or_default();
+        let path = url.path();
+        let mut matching: Vec<&Cookie> = self
+            .entries
+            .values()
+            .filter(|c| !c.is_expired(now) && c.matches(host, path))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first, matching browser precedence for same-name cookies.
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        let header = matching
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Some(header)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Parse one `Set-Cookie` header value into a structured [`Cookie`].
+/// Returns `None` for a malformed header (no `name=value` pair).
+pub fn parse_set_cookie(header: &str, request_url: &Url) -> Option<Cookie> {
+    let mut parts = header.split(';').map(str::trim);
+    let (name, value) = {
+        let pair = parts.next()?;
+        let mut kv = pair.splitn(2, '=');
+        (kv.next()?.to_string(), kv.next().unwrap_or("").to_string())
+    };
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut domain = request_url.host_str().unwrap_or_default().to_string();
+    let mut host_only = true;
+    let mut path = default_path(request_url.path());
+    let mut expires_at = None;
+    let mut max_age = None;
+    let mut secure = false;
+    let mut http_only = false;
+    let mut same_site = None;
+
+    for attr in parts {
+        let mut kv = attr.splitn(2, '=');
+        let key = kv.next().unwrap_or("").trim();
+        let val = kv.next().map(str::trim);
+        match key.to_ascii_lowercase().as_str() {
+            "domain" => {
+                if let Some(v) = val {
+                    domain = v.trim_start_matches('.').to_string();
+                    host_only = false;
+                }
+            }
+            "path" => {
+                if let Some(v) = val {
+                    path = v.to_string();
+                }
+            }
+            "expires" => {
+                if let Some(v) = val {
+                    expires_at = parse_http_date(v);
+                }
+            }
+            "max-age" => {
+                if let Some(v) = val {
+                    max_age = v.parse::<i64>().ok();
+                }
+            }
+            "secure" => secure = true,
+            "httponly" => http_only = true,
+            "samesite" => {
+                same_site = val.map(|v| match v.to_ascii_lowercase().as_str() {
+                    "strict" => SameSite::Strict,
+                    "none" => SameSite::None,
+                    _ => SameSite::Lax,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Max-Age takes precedence over Expires per RFC 6265 §5.3.
+    if let Some(max_age) = max_age {
+        let now = now_secs() as i64;
+        expires_at = Some(if max_age <= 0 { 0 } else { (now + max_age) as u64 });
+    }
+
+    Some(Cookie {
+        name,
+        value,
+        domain,
+        path,
+        expires_at,
+        secure,
+        http_only,
+        same_site,
+        host_only,
+    })
+}
+
+/// Default-Path algorithm from RFC 6265 §5.1.4: the request path up to (but
+/// not including) the last `/`, or `/` if there is none.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(idx) => request_path[..idx].to_string(),
+    }
+}
+
+/// Parse the subset of HTTP-date formats a `Set-Cookie: Expires=` attribute
+/// actually shows up in (RFC 1123, e.g. "Wed, 21 Oct 2015 07:28:00 GMT") into
+/// a Unix timestamp. Returns `None` on anything else rather than guessing.
+fn parse_http_date(s: &str) -> Option<u64> {
+    let s = s.trim().trim_end_matches("GMT").trim();
+    let mut it = s.split_whitespace().peekable();
+
+    // Both formats lead with a weekday token ("Wed," / "Wednesday,") that
+    // isn't part of the date itself -- discard it before the day/month/year
+    // parsing below, which otherwise mistakes it for the day.
+    if it.peek().is_some_and(|tok| tok.ends_with(',')) {
+        it.next();
+    }
+
+    let day_month_year = it.next()?; // e.g. "21" when comma-separated form is "Wed, 21 Oct 2015"
+    let (day, month, year) = if day_month_year.contains('-') {
+        // "21-Oct-2015" (old RFC 850 style)
+        let mut parts = day_month_year.split('-');
+        (parts.next()?, parts.next()?, parts.next()?)
+    } else {
+        let month = it.next()?;
+        let year = it.next()?;
+        (day_month_year, month, year)
+    };
+    let time = it.next()?;
+    let day: u64 = day.parse().ok()?;
+    let month = month_number(month)?;
+    let mut year: i64 = year.parse().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    let mut time_parts = time.split(':');
+    let hour: u64 = time_parts.next()?.parse().ok()?;
+    let minute: u64 = time_parts.next()?.parse().ok()?;
+    let second: u64 = time_parts.next()?.parse().ok()?;
+
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+fn month_number(name: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let lower = name.to_ascii_lowercase();
+    MONTHS
+        .iter()
+        .position(|m| lower.starts_with(m))
+        .map(|idx| idx as u64 + 1)
+}
+
+/// Howard Hinnant's days-from-civil algorithm, giving the Unix day count for
+/// a Gregorian calendar date; avoids pulling in a full date/time crate just
+/// to turn `Expires=` into a timestamp.
+fn days_from_civil(y: i64, m: u64, d: u64) -> u64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = (y - era * 400) as u64; // [0, 399]
+    let mp = (m + 9) % 12; // [0, 11]
+    let doy = (153 * mp + 2) / 5 + d - 1; // [0, 365]
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy; // [0, 146096]
+    let days_since_epoch = era * 146_097 + doe as i64 - 719_468;
+    days_since_epoch.max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_http_date_rfc1123_example() {
+        // The literal example from RFC 6265 §5.2.1.
+        assert_eq!(
+            parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+    }
+
+    #[test]
+    fn parse_http_date_rfc850_example() {
+        assert_eq!(
+            parse_http_date("Wednesday, 21-Oct-15 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+    }
+
+    #[test]
+    fn parse_http_date_rejects_garbage() {
+        assert_eq!(parse_http_date("not a date"), None);
+    }
+
+    #[test]
+    fn days_from_civil_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+    }
+
+    #[test]
+    fn days_from_civil_known_date() {
+        // 2015-10-21, per the RFC 6265 example above.
+        assert_eq!(days_from_civil(2015, 10, 21), 1_445_412_480 / 86_400);
+    }
+
+    #[test]
+    fn default_path_strips_last_segment() {
+        assert_eq!(default_path("/a/b/c"), "/a/b");
+        assert_eq!(default_path("/a"), "/");
+        assert_eq!(default_path("/"), "/");
+        assert_eq!(default_path(""), "/");
+    }
+
+    fn cookie(domain: &str, path: &str, host_only: bool) -> Cookie {
+        Cookie {
+            name: "session".to_string(),
+            value: "abc".to_string(),
+            domain: domain.to_string(),
+            path: path.to_string(),
+            expires_at: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            host_only,
+        }
+    }
+
+    #[test]
+    fn host_only_cookie_does_not_match_subdomain() {
+        let c = cookie("foo.example.com", "/", true);
+        assert!(c.matches("foo.example.com", "/"));
+        assert!(!c.matches("bar.foo.example.com", "/"));
+    }
+
+    #[test]
+    fn domain_cookie_matches_subdomain() {
+        let c = cookie("foo.example.com", "/", false);
+        assert!(c.matches("foo.example.com", "/"));
+        assert!(c.matches("bar.foo.example.com", "/"));
+    }
+
+    #[test]
+    fn parse_set_cookie_without_domain_is_host_only() {
+        let url = Url::parse("https://foo.example.com/app").unwrap();
+        let cookie = parse_set_cookie("session=abc", &url).unwrap();
+        assert!(cookie.host_only);
+        assert_eq!(cookie.domain, "foo.example.com");
+    }
+
+    #[test]
+    fn parse_set_cookie_with_domain_is_not_host_only() {
+        let url = Url::parse("https://foo.example.com/app").unwrap();
+        let cookie = parse_set_cookie("session=abc; Domain=example.com", &url).unwrap();
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.domain, "example.com");
+    }
+}
@@ -0,0 +1,131 @@
+//! A minimal OIDC-style identity assertion: other local processes can ask
+//! this process to mint a short-lived, signed statement of "this request
+//! comes from node X", instead of re-implementing the
+//! sign→verify→make-message dance `auto_login` already does for the web2
+//! login flow.
+//!
+//! Reachable only via process-to-process `Request`/`Response`
+//! ([`IdentityRequest`]/[`IdentityResponse`]), never over HTTP: the HTTP
+//! paths this process binds are either deliberately open to anonymous
+//! proxied browsers (`/`) or gated behind node-session auth (the debug
+//! log), neither of which is an appropriate trust boundary for minting
+//! "this request comes from node X" assertions.
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyperware_process_lib::{get_blob, Address, Request};
+
+use crate::hyperware::process::sign;
+
+/// How long a minted identity token stays valid for.
+const TOKEN_LIFETIME_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityClaims {
+    pub sub: String,
+    pub aud: String,
+    pub nonce: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// Request shape for the process-to-process identity surface.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum IdentityRequest {
+    IssueIdentityToken { audience: String, nonce: String },
+    VerifyIdentityToken { token: String, audience: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum IdentityResponse {
+    Token(String),
+    Verified(IdentityClaims),
+    Err(String),
+}
+
+/// Dispatch an [`IdentityRequest`] from another local process.
+pub fn handle(our: &Address, request: IdentityRequest) -> IdentityResponse {
+    match request {
+        IdentityRequest::IssueIdentityToken { audience, nonce } => {
+            match issue(our, &audience, &nonce) {
+                Ok(token) => IdentityResponse::Token(token),
+                Err(e) => IdentityResponse::Err(e.to_string()),
+            }
+        }
+        IdentityRequest::VerifyIdentityToken { token, audience } => {
+            match verify(our, &token, &audience) {
+                Ok(claims) => IdentityResponse::Verified(claims),
+                Err(e) => IdentityResponse::Err(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Mint a signed identity token asserting `our`'s node id (`sub`), scoped to
+/// `audience` (`aud`) and the caller-supplied `nonce`. Returns a compact
+/// `<base64 claims>.<base64 signature>` string -- a JWT's shape without
+/// pulling in a JWT crate for one small claim set.
+fn issue(our: &Address, audience: &str, nonce: &str) -> anyhow::Result<String> {
+    let target = Address::new(our.node(), ("sign", "sign", "sys"));
+    let iat = now_secs();
+    let claims = IdentityClaims {
+        sub: our.node().to_string(),
+        aud: audience.to_string(),
+        nonce: nonce.to_string(),
+        iat,
+        exp: iat + TOKEN_LIFETIME_SECS,
+    };
+    let claims_bytes = serde_json::to_vec(&claims)?;
+
+    Request::to(target)
+        .blob_bytes(claims_bytes.clone())
+        .body(sign::Request::NetKeySign)
+        .send_and_await_response(10)??;
+    let signature_blob = get_blob().unwrap();
+
+    Ok(format!(
+        "{}.{}",
+        general_purpose::STANDARD.encode(&claims_bytes),
+        general_purpose::STANDARD.encode(&signature_blob.bytes)
+    ))
+}
+
+/// Verify a token minted by [`issue`]: checks the node-key signature via
+/// `NetKeyVerify`, then that it hasn't expired and is scoped to `audience`.
+fn verify(our: &Address, token: &str, audience: &str) -> anyhow::Result<IdentityClaims> {
+    let (claims_b64, signature_b64) = token
+        .split_once('.')
+        .ok_or_else(|| anyhow::anyhow!("malformed identity token"))?;
+    let claims_bytes = general_purpose::STANDARD.decode(claims_b64)?;
+    let signature = general_purpose::STANDARD.decode(signature_b64)?;
+    let claims: IdentityClaims = serde_json::from_slice(&claims_bytes)?;
+
+    let target = Address::new(our.node(), ("sign", "sign", "sys"));
+    Request::to(target)
+        .blob_bytes(claims_bytes)
+        .body(sign::Request::NetKeyVerify(sign::NetKeyVerifyRequest {
+            node: claims.sub.clone(),
+            signature,
+        }))
+        .send_and_await_response(10)??;
+
+    let now = now_secs();
+    if now >= claims.exp {
+        return Err(anyhow::anyhow!("identity token expired"));
+    }
+    if claims.aud != audience {
+        return Err(anyhow::anyhow!(
+            "identity token issued for a different audience"
+        ));
+    }
+    Ok(claims)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
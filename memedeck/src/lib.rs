@@ -7,8 +7,8 @@ use hyperware_process_lib::http::server::{send_response, HttpBindingConfig, WsBi
 use hyperware_process_lib::http::{Method, StatusCode};
 use hyperware_process_lib::logging::{init_logging, Level};
 use hyperware_process_lib::{
-    await_message, call_init, get_blob, homepage, http, kiprintln, Address, Capability, Message,
-    Request,
+    await_message, call_init, get_blob, get_typed_state, homepage, http, kiprintln, set_state,
+    Address, Capability, Message, Request, Response,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -16,17 +16,30 @@ use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::Url;
 
+mod auth;
+mod cookie;
+mod identity;
+mod network_log;
 mod proxy;
+mod siwe;
+mod ws;
+
+use auth::ReauthGuard;
+use cookie::CookieJar;
+use network_log::NetworkLog;
+use siwe::SignInMessage;
+use ws::WsProxy;
 
 const WEB2_URL: &str = "https://hyperware.memedeck.xyz";
 const WEB2_LOGIN_ENDPOINT: &str = "https://api.memedeck.xyz/v2/auth/hyperware/login";
 const PACKAGE_PATH: &str = "/app:memedeck:meme-deck.os";
+/// Non-proxied route for `FrontendRequest`s (currently just `Debug`), bound
+/// ahead of the catch-all `/` proxy path.
+const API_DEBUG_PATH: &str = "/api/memedeck/debug";
 
 // const WEB2_URL: &str = "http://localhost:3000";
 // const WEB2_LOGIN_ENDPOINT: &str = "http://localhost:8080/v2/auth/hyperware/login";
 
-const WEB2_LOGIN_NONCE: &str = "951f64b8-5905-47f8-b12c-3ca8f53119f2";
-
 wit_bindgen::generate!({
     path: "target/wit",
     world: "memedeck-template-dot-os-v0",
@@ -42,11 +55,73 @@ enum FrontendRequest {
     Debug(String),
 }
 
+
+/// The original shape of persisted state: a single opaque `Cookie:` header
+/// string. Kept only so [`VersionedState::load`] can migrate it forward.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProxyStateV1 {
+    pub cookie: Option<String>,
+}
+
+/// Current state: a full cookie jar instead of one opaque string, so we can
+/// hold more than one upstream cookie and honor their `Domain`/`Path`/expiry.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProxyStateV2 {
+    pub jar: CookieJar,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-struct LoginMessage {
-    pub site: String,
-    pub time: u64,
-    pub nonce: Option<String>,
+#[serde(tag = "version")]
+enum VersionedState {
+    V1(ProxyStateV1),
+    /// State fully stored in memory, persisted using serde_json.
+    /// Future state version will use SQLite.
+    V2(ProxyStateV2),
+}
+
+impl VersionedState {
+    fn load() -> Self {
+        match get_typed_state(|bytes| serde_json::from_slice(bytes)) {
+            Some(Self::V1(v1)) => {
+                // Migrate the old single-cookie string into the jar, keyed
+                // against the web2 origin since that's all V1 ever talked to.
+                let mut jar = CookieJar::new();
+                if let (Some(raw), Ok(url)) = (v1.cookie, Url::parse(WEB2_URL)) {
+                    if let Some(cookie) = cookie::parse_set_cookie(&raw, &url) {
+                        jar.store(cookie);
+                    }
+                }
+                Self::V2(ProxyStateV2 { jar })
+            }
+            Some(v2 @ Self::V2(_)) => v2,
+            None => Self::V2(ProxyStateV2 {
+                jar: CookieJar::new(),
+            }),
+        }
+    }
+
+    fn save(&self) {
+        set_state(&serde_json::to_vec(&self).expect("Failed to serialize state!"));
+    }
+
+    fn jar_mut(&mut self) -> &mut CookieJar {
+        match self {
+            Self::V1(_) => unreachable!("load() always migrates V1 to V2"),
+            Self::V2(ps) => &mut ps.jar,
+        }
+    }
+
+    fn jar(&self) -> &CookieJar {
+        match self {
+            Self::V1(_) => unreachable!("load() always migrates V1 to V2"),
+            Self::V2(ps) => &ps.jar,
+        }
+    }
+
+    fn wipe_cookies(&mut self) {
+        self.jar_mut().clear();
+        self.save();
+    }
 }
 
 const ICON: &str = include_str!("icon");
@@ -58,23 +133,47 @@ fn initialize(our: Address) {
 
     homepage::add_to_homepage("Memedeck", Some(ICON), Some("/home"), None);
 
-    let mut cookie = None;
+    let mut state = VersionedState::load();
+    state.wipe_cookies();
 
     let mut http_server = http::server::HttpServer::new(5);
     let http_config = HttpBindingConfig::default().secure_subdomain(false);
+    // The catch-all `/` proxy is deliberately open to anonymous visitors --
+    // that's the point of node-wide login -- but the debug log exposes a
+    // user's own proxied traffic (URLs, header values, timings) and must not
+    // be world-readable the same way. Require an authenticated node session
+    // for it specifically.
+    let debug_http_config = http_config.clone().authenticated(true);
 
+    http_server
+        .bind_http_path(API_DEBUG_PATH, debug_http_config)
+        .unwrap();
     http_server.bind_http_path("/", http_config).unwrap();
     http_server
         .bind_ws_path("/", WsBindingConfig::default())
         .unwrap();
 
-    main_loop(&our, &mut http_server, &mut cookie);
+    let mut ws_proxy = WsProxy::new();
+    let mut network_log = NetworkLog::new();
+    let mut reauth_guard = ReauthGuard::new();
+
+    main_loop(
+        &our,
+        &mut http_server,
+        &mut state,
+        &mut ws_proxy,
+        &mut network_log,
+        &mut reauth_guard,
+    );
 }
 
 fn main_loop(
     our: &Address,
     http_server: &mut http::server::HttpServer,
-    cookie: &mut Option<String>,
+    state: &mut VersionedState,
+    ws_proxy: &mut WsProxy,
+    network_log: &mut NetworkLog,
+    reauth_guard: &mut ReauthGuard,
 ) {
     loop {
         match await_message() {
@@ -93,20 +192,50 @@ fn main_loop(
                 if source.node() != our.node {
                     continue;
                 }
-                let _ = handle_request(our, &source, &body, capabilities, http_server, cookie);
+                let _ = handle_request(
+                    our,
+                    &source,
+                    &body,
+                    capabilities,
+                    http_server,
+                    state,
+                    ws_proxy,
+                    network_log,
+                    reauth_guard,
+                );
+                // Piggyback on message traffic to ping idle upstream WS
+                // connections; this process has no standalone timer/tick, so
+                // every local request we handle doubles as our keepalive clock.
+                ws_proxy.send_keepalives();
             }
             _ => continue, // ignore responses
         }
     }
 }
 
+/// Derive the `ws(s)://` endpoint at `path` on the web2 origin [`WEB2_URL`].
+fn web2_ws_url(path: &str) -> anyhow::Result<Url> {
+    let mut url = Url::parse(WEB2_URL)?;
+    let ws_scheme = match url.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    url.set_scheme(ws_scheme)
+        .map_err(|_| anyhow::anyhow!("failed to set ws scheme"))?;
+    url.set_path(path);
+    Ok(url)
+}
+
 fn handle_request(
     our: &Address,
     source: &Address,
     body: &[u8],
     _capabilities: Vec<Capability>,
     http_server: &mut http::server::HttpServer,
-    cookie: &mut Option<String>,
+    state: &mut VersionedState,
+    ws_proxy: &mut WsProxy,
+    network_log: &mut NetworkLog,
+    reauth_guard: &mut ReauthGuard,
 ) -> anyhow::Result<()> {
     // source node is ALWAYS ourselves since networking is disabled
     if source.process == "http-server:distro:sys" {
@@ -114,11 +243,50 @@ fn handle_request(
         let server_request = http_server.parse_request(body).unwrap();
         match server_request {
             http::server::HttpServerRequest::Http(request) => {
-                handle_page_request(our, &request, cookie)?;
+                handle_page_request(our, &request, state, network_log, reauth_guard)?;
+            }
+            http::server::HttpServerRequest::WebSocketOpen { path, channel_id } => {
+                let url = web2_ws_url(&path)?;
+                ws_proxy.open(channel_id, &url, state.jar())?;
+            }
+            http::server::HttpServerRequest::WebSocketPush {
+                channel_id,
+                message_type,
+            } => {
+                let blob = get_blob().unwrap();
+                ws_proxy.forward_to_upstream(channel_id, message_type, blob.bytes)?;
+            }
+            http::server::HttpServerRequest::WebSocketClose(channel_id) => {
+                ws_proxy.close_from_browser(channel_id);
             }
-            // TODO handle websockets
-            _ => (),
         };
+    } else if source.process == "http-client:distro:sys" {
+        // frames/close notifications arriving from an upstream connection
+        // we opened on the web2 backend's behalf
+        match hyperware_process_lib::http::client::parse_websocket_message(body)? {
+            hyperware_process_lib::http::client::WsClientMessage::Push {
+                channel_id,
+                message_type,
+            } => {
+                let blob = get_blob().unwrap();
+                ws_proxy.forward_to_browser(http_server, channel_id, message_type, blob.bytes);
+            }
+            hyperware_process_lib::http::client::WsClientMessage::Close { channel_id } => {
+                ws_proxy.close_from_upstream(http_server, channel_id);
+            }
+        }
+    } else {
+        // Any other local process (source.node() == our.node is already
+        // guaranteed by main_loop). This is the identity-provider surface:
+        // unlike the HTTP paths above, which must stay reachable by
+        // anonymous proxied browsers, identity issuance/verification must
+        // NOT be reachable over HTTP at all, so it's only ever exposed
+        // process-to-process here.
+        let request: identity::IdentityRequest = serde_json::from_slice(body)?;
+        let response = identity::handle(our, request);
+        Response::new()
+            .body(serde_json::to_vec(&response)?)
+            .send()?;
     };
     Ok(())
 }
@@ -126,30 +294,77 @@ fn handle_request(
 fn handle_page_request(
     our: &Address,
     http_request: &http::server::IncomingHttpRequest,
-    cookie: &mut Option<String>,
+    state: &mut VersionedState,
+    network_log: &mut NetworkLog,
+    reauth_guard: &mut ReauthGuard,
 ) -> anyhow::Result<()> {
-    match cookie {
-        Some(cookie) => {
-            return proxy::run_proxy(&http_request, WEB2_URL, &cookie, PACKAGE_PATH);
+    if let Ok(url) = http_request.url() {
+        if url.path() == API_DEBUG_PATH {
+            let filter = get_blob()
+                .and_then(|blob| serde_json::from_slice::<FrontendRequest>(&blob.bytes).ok());
+            return handle_debug_request(&url, filter, network_log);
         }
-        None => {
-            let new_cookie = auto_login(our)?;
-            *cookie = new_cookie;
+    }
+
+    if state.jar().is_empty() {
+        let new_cookie = auto_login(our, state.jar_mut())?;
+        state.save();
 
-            send_refresh_response(1, cookie.clone().unwrap())?;
-            return Ok(());
+        if let Some(cookie) = new_cookie {
+            send_refresh_response(1, cookie)?;
         }
+        return Ok(());
     }
+
+    let jar = state.jar_mut();
+    proxy::run_proxy(&http_request, WEB2_URL, jar, PACKAGE_PATH, network_log, |jar| {
+        if !reauth_guard.try_acquire() {
+            // another request is already re-authenticating; don't pile on
+            return Ok(false);
+        }
+        jar.clear();
+        let result = auto_login(our, jar);
+        reauth_guard.release();
+        Ok(result?.is_some())
+    })?;
+    state.save();
+    Ok(())
 }
 
-fn auto_login(our: &Address) -> anyhow::Result<Option<String>> {
-    let target = Address::new(our.node(), ("sign", "sign", "sys"));
-    let body = LoginMessage {
-        site: WEB2_URL.to_string(),
-        nonce: Some(WEB2_LOGIN_NONCE.to_string()),
-        time: get_now(),
+/// Serve `FrontendRequest::Debug`: the recent network log as JSON. The
+/// request body may carry `FrontendRequest::Debug(path_prefix)` to filter by
+/// URL; `?status_class=4xx` in the query string filters by status class.
+fn handle_debug_request(
+    url: &Url,
+    filter: Option<FrontendRequest>,
+    network_log: &NetworkLog,
+) -> anyhow::Result<()> {
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    let path_prefix = match &filter {
+        Some(FrontendRequest::Debug(prefix)) if !prefix.is_empty() => Some(prefix.as_str()),
+        _ => None,
     };
-    let body_bytes = serde_json::to_vec(&body)?;
+    let records = network_log.recent(params.get("status_class").map(String::as_str), path_prefix);
+    send_json_response(StatusCode::OK, &records)
+}
+
+fn send_json_response<T: serde::Serialize>(status: StatusCode, data: &T) -> anyhow::Result<()> {
+    let json_data = serde_json::to_vec(data)?;
+    send_response(
+        status,
+        Some(HashMap::from([(
+            "Content-Type".to_string(),
+            "application/json".to_string(),
+        )])),
+        json_data,
+    );
+    Ok(())
+}
+
+fn auto_login(our: &Address, jar: &mut CookieJar) -> anyhow::Result<Option<String>> {
+    let target = Address::new(our.node(), ("sign", "sign", "sys"));
+    let message = SignInMessage::new(WEB2_URL, our.node(), WEB2_LOGIN_ENDPOINT);
+    let body_bytes = message.to_canonical_string().into_bytes();
 
     let request_result = Request::to(target.clone())
         .blob_bytes(body_bytes.clone())
@@ -175,12 +390,13 @@ fn auto_login(our: &Address) -> anyhow::Result<Option<String>> {
         .send_and_await_response(10)??;
     let message_blob = get_blob().unwrap();
 
-    let new_cookie = attempt_login(our, message_blob.bytes, signature_blob.bytes)?;
+    let new_cookie = attempt_login(our, jar, message_blob.bytes, signature_blob.bytes)?;
     Ok(new_cookie)
 }
 
 fn attempt_login(
     our: &Address,
+    jar: &mut CookieJar,
     message: Vec<u8>,
     signature: Vec<u8>,
     //signature_response: SignResponse,
@@ -205,34 +421,66 @@ fn attempt_login(
     let json_bytes = serde_json::to_vec(&json)?;
     let url = Url::parse(WEB2_LOGIN_ENDPOINT).unwrap();
 
-    let res = match send_request_await_response(
-        Method::POST,
-        url,
-        Some(json_headers),
-        5000,
-        json_bytes,
-    ) {
-        Ok(res) => res,
-        Err(e) => {
-            kiprintln!("Failed to send request: {:?}", e);
-            return Err(anyhow::anyhow!("Failed to send request"));
-        }
-    };
-    let resbody = res.body();
+    // The login endpoint itself may bounce through a redirect (e.g. onto a
+    // canonical host), so follow it the same way `proxy::run_proxy` follows
+    // redirects from the backend, instead of treating a 3xx as a failure.
+    let mut set_cookie_headers = Vec::new();
+    let (res, _final_url) = proxy::follow_redirects(&url, Method::POST, json_bytes, |method, url, body| {
+        let res = match send_request_await_response(
+            method.clone(),
+            url.clone(),
+            Some(json_headers.clone()),
+            5000,
+            body.to_vec(),
+        ) {
+            Ok(res) => res,
+            Err(e) => {
+                kiprintln!("Failed to send request: {:?}", e);
+                return Err(anyhow::anyhow!("Failed to send request"));
+            }
+        };
+
+        // The login endpoint may set its own upstream cookies (e.g. a
+        // refresh token) on any hop; stash them to absorb into the jar once
+        // we've landed on the final response.
+        set_cookie_headers.extend(
+            res.headers()
+                .get_all("set-cookie")
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .map(str::to_string),
+        );
+
+        Ok(proxy::HopResponse {
+            status: res.status(),
+            headers: res
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                .collect(),
+            body: res.body().to_vec(),
+        })
+    })?;
+    let resbody = &res.body;
     let resjson = serde_json::from_slice::<serde_json::Value>(resbody)?;
     kiprintln!("resjson: {:?}", resjson);
     let res_token = resjson.get("cookie");
 
+    let web2_url = Url::parse(WEB2_URL).unwrap();
+    jar.absorb_set_cookie_headers(set_cookie_headers.iter().map(String::as_str), &web2_url);
+
     match res_token {
         None => {
             kiprintln!("Signature verification failed");
             Err(anyhow::anyhow!("Signature verification failed"))
         }
         Some(cookie_value) => {
-            let cookie = format!(
-                "hyperware_token={}; path=/;",
-                serde_json::from_value::<String>(cookie_value.clone())?
+            let token = serde_json::from_value::<String>(cookie_value.clone())?;
+            jar.absorb_set_cookie_headers(
+                [format!("hyperware_token={}; Path=/", token).as_str()],
+                &web2_url,
             );
+            let cookie = format!("hyperware_token={}; path=/;", token);
             kiprintln!("Cookie fetched successfully: {:?}", cookie);
             Ok(Some(cookie))
         }
@@ -246,6 +494,15 @@ fn get_now() -> u64 {
         .as_secs()
 }
 
+/// Millisecond-resolution clock, used only for timing proxied exchanges --
+/// [`get_now`] is deliberately second-resolution for cookie expiry math.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 fn send_refresh_response(delay_seconds: u32, cookie: String) -> anyhow::Result<()> {
     // Get our address to construct a proper path
     let home_path = format!("{}/home", PACKAGE_PATH); // Use the same path defined in initialize()
@@ -0,0 +1,84 @@
+//! A small devtools-style "Network" panel for the proxy: a bounded ring
+//! buffer of recent proxied exchanges, so developers debugging the
+//! auth-and-proxy flow have something better to look at than scattered
+//! `kiprintln!` calls.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+
+/// How many exchanges to remember. Old entries are dropped once this fills.
+const CAPACITY: usize = 200;
+
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub url: String,
+    pub request_headers: HashMap<String, String>,
+    pub status: u16,
+    pub response_headers: HashMap<String, String>,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    pub duration_ms: u64,
+    pub timestamp: u64,
+}
+
+/// Bounded ring buffer of recent [`Exchange`] records, held transiently in
+/// memory for the lifetime of the process (not persisted: it's a debugging
+/// aid, not session state).
+#[derive(Default)]
+pub struct NetworkLog {
+    entries: std::collections::VecDeque<Exchange>,
+}
+
+impl NetworkLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, exchange: Exchange) {
+        if self.entries.len() >= CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(exchange);
+    }
+
+    /// Recent records, most recent first, optionally filtered by status
+    /// class (e.g. `"4xx"`) and/or a prefix matched against the URL's path.
+    pub fn recent(&self, status_class: Option<&str>, path_prefix: Option<&str>) -> Vec<&Exchange> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| status_class.map_or(true, |class| matches_status_class(e.status, class)))
+            .filter(|e| {
+                path_prefix.map_or(true, |prefix| {
+                    Url::parse(&e.url).map_or(false, |u| u.path().starts_with(prefix))
+                })
+            })
+            .collect()
+    }
+}
+
+fn matches_status_class(status: u16, class: &str) -> bool {
+    let Some(digit) = class.chars().next().and_then(|c| c.to_digit(10)) else {
+        return true;
+    };
+    (status as u32) / 100 == digit
+}
+
+/// Redact sensitive header values before they ever enter the log, so the
+/// debug endpoint can't leak auth/session cookies.
+pub fn redact_headers(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            if REDACTED_HEADERS.contains(&k.to_ascii_lowercase().as_str()) {
+                (k.clone(), "<redacted>".to_string())
+            } else {
+                (k.clone(), v.clone())
+            }
+        })
+        .collect()
+}
@@ -1,60 +1,426 @@
 use std::collections::HashMap;
+use std::io::{Read, Write};
 
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use hyperware_process_lib::http::server::IncomingHttpRequest;
+use hyperware_process_lib::http::{Method, StatusCode};
 use url::Url;
 
 use hyperware_process_lib::{
     get_blob, http::client::send_request_await_response, http::server::send_response,
 };
 
+use crate::cookie::CookieJar;
+use crate::network_log::{redact_headers, Exchange, NetworkLog};
+use crate::{get_now, now_ms};
+
+/// How many redirect hops we'll follow before giving up, mirroring the
+/// default most HTTP client libraries ship with.
+const MAX_REDIRECTS: u32 = 10;
+
 fn replace_domain(original_url: &Url, new_domain: &str) -> anyhow::Result<Url> {
     let mut new_url = Url::parse(new_domain)?;
     new_url.set_path(original_url.path());
     Ok(new_url)
 }
 
+/// Map an upstream `Location` back onto the Hyperware origin the browser
+/// actually talked to, so a redirect that escapes our proxy never points the
+/// browser at the web2 host directly.
+fn rewrite_location_for_browser(location: &Url, browser_url: &Url) -> anyhow::Result<Url> {
+    let mut local = browser_url.clone();
+    local.set_path(location.path());
+    local.set_query(location.query());
+    Ok(local)
+}
+
+/// Look up a header by name, ignoring case, since the headers here were
+/// collected into a plain `HashMap` that preserves whatever case the
+/// upstream sent them in.
+fn header_ci<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Transparently decode a `gzip`/`deflate` response body so the rest of the
+/// proxy (and eventually body rewriting) can work with plain bytes instead
+/// of an opaque compressed blob. `identity`/absent encodings pass through
+/// untouched. Strips `Content-Encoding` and fixes `Content-Length` in place
+/// when a decode happens.
+fn decode_body(headers: &mut HashMap<String, String>, body: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    let encoding = header_ci(headers, "content-encoding").map(str::to_ascii_lowercase);
+    let decoded = match encoding.as_deref() {
+        Some("gzip") => {
+            let mut out = Vec::new();
+            GzDecoder::new(&body[..])
+                .read_to_end(&mut out)
+                .map_err(|e| anyhow::anyhow!("corrupt gzip response body: {e}"))?;
+            Some(out)
+        }
+        Some("deflate") => {
+            let mut out = Vec::new();
+            DeflateDecoder::new(&body[..])
+                .read_to_end(&mut out)
+                .map_err(|e| anyhow::anyhow!("corrupt deflate response body: {e}"))?;
+            Some(out)
+        }
+        _ => None,
+    };
+
+    let Some(decoded) = decoded else {
+        return Ok(body);
+    };
+
+    headers.retain(|k, _| !k.eq_ignore_ascii_case("content-encoding"));
+    let content_length_key = headers
+        .keys()
+        .find(|k| k.eq_ignore_ascii_case("content-length"))
+        .cloned();
+    if let Some(key) = content_length_key {
+        headers.insert(key, decoded.len().to_string());
+    }
+    Ok(decoded)
+}
+
+/// Below this many bytes, gzipping a response back to the browser costs more
+/// in CPU than it saves in transfer size, so we just send it as-is.
+const MIN_COMPRESSIBLE_LEN: usize = 1024;
+
+const COMPRESSIBLE_CONTENT_TYPES: &[&str] = &["text/", "application/json", "application/javascript"];
+
+/// Re-compress a response for the browser if it asked for `gzip` via
+/// `Accept-Encoding` and the body is a large-enough, text-like payload to be
+/// worth it. Mirrors [`decode_body`] in reverse.
+fn encode_body_for_browser(
+    headers: &mut HashMap<String, String>,
+    body: Vec<u8>,
+    browser_accept_encoding: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
+    let accepts_gzip = browser_accept_encoding
+        .map(|v| v.to_ascii_lowercase().contains("gzip"))
+        .unwrap_or(false);
+    let content_type = header_ci(headers, "content-type")
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let is_compressible = COMPRESSIBLE_CONTENT_TYPES
+        .iter()
+        .any(|prefix| content_type.starts_with(prefix));
+    let already_encoded = header_ci(headers, "content-encoding").is_some();
+
+    if !accepts_gzip || !is_compressible || already_encoded || body.len() < MIN_COMPRESSIBLE_LEN {
+        return Ok(body);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&body)?;
+    let compressed = encoder.finish()?;
+
+    headers.insert("content-encoding".to_string(), "gzip".to_string());
+    let content_length_key = headers
+        .keys()
+        .find(|k| k.eq_ignore_ascii_case("content-length"))
+        .cloned();
+    if let Some(key) = content_length_key {
+        headers.insert(key, compressed.len().to_string());
+    }
+    Ok(compressed)
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// One hop's worth of response, as handed back to [`follow_redirects`] by its
+/// caller-supplied `do_request`. Headers are kept as a `Vec` rather than a
+/// `HashMap` so that repeated header names -- `Set-Cookie` above all -- don't
+/// collapse into just the last value.
+pub(crate) struct HopResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Drive `method`/`body` against `base_url` through its redirect chain (up to
+/// [`MAX_REDIRECTS`] hops), applying the same method/body downgrade rules a
+/// browser would: a 303 always becomes a bodyless GET; a 301/302 downgrades a
+/// POST to a bodyless GET; a 307/308 preserves both. `do_request` performs
+/// the actual I/O for one hop -- along with anything hop-specific the caller
+/// needs alongside it, like injecting jar cookies or appending to a network
+/// log -- and hands back just enough of the response for this loop to decide
+/// whether to keep following it. Returns the terminal (non-redirect, or
+/// redirect-with-no-`Location`) response together with the URL it came from.
+pub(crate) fn follow_redirects(
+    base_url: &Url,
+    mut method: Method,
+    mut body: Vec<u8>,
+    mut do_request: impl FnMut(&Method, &Url, &[u8]) -> anyhow::Result<HopResponse>,
+) -> anyhow::Result<(HopResponse, Url)> {
+    let mut url = base_url.clone();
+    let mut hops = 0;
+    loop {
+        let response = do_request(&method, &url, &body)?;
+        let location = find_header(&response.headers, "location").map(str::to_string);
+
+        if !is_redirect(response.status) || location.is_none() {
+            return Ok((response, url));
+        }
+
+        hops += 1;
+        if hops > MAX_REDIRECTS {
+            return Err(anyhow::anyhow!(
+                "too many redirects ({} hops) at {}",
+                hops - 1,
+                url
+            ));
+        }
+
+        url = url.join(location.as_deref().unwrap())?;
+        match response.status {
+            // 303 always becomes GET and drops the body.
+            StatusCode::SEE_OTHER => {
+                method = Method::GET;
+                body = Vec::new();
+            }
+            // 301/302 downgrade POST to GET for compatibility with how
+            // browsers have historically handled these.
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND if method == Method::POST => {
+                method = Method::GET;
+                body = Vec::new();
+            }
+            // 307/308 preserve method and body.
+            _ => {}
+        }
+    }
+}
+
+/// Outcome of following a request to wherever its redirect chain ends,
+/// decoupled from the client library's `Response` type so it can be built
+/// and re-tried without fighting borrow lifetimes.
+struct FetchResult {
+    status: StatusCode,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+    location: Option<String>,
+    /// Final URL actually used to resolve a relative `Location`, if any.
+    final_url: Url,
+    /// Whether the redirect chain ended on a different URL than the one it
+    /// started from; a chain that loops back to its starting URL reads `false`.
+    redirected: bool,
+}
+
+fn is_auth_failure(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+}
+
+/// Some backend endpoints report an expired session with a `200` and a JSON
+/// error body instead of a `401`/`403`, so a status-code check alone misses
+/// them. Cheaply sniff the first slice of the *decoded* body for the phrase
+/// -- callers must run this after [`decode_body`], since the backend may
+/// gzip/deflate error bodies the same as everything else.
+const SESSION_EXPIRED_NEEDLE: &str = "session expired";
+const SESSION_EXPIRED_SNIFF_LEN: usize = 512;
+
+fn body_signals_session_expired(body: &[u8]) -> bool {
+    let sniff = &body[..body.len().min(SESSION_EXPIRED_SNIFF_LEN)];
+    let Ok(text) = std::str::from_utf8(sniff) else {
+        return false;
+    };
+    text.to_ascii_lowercase().contains(SESSION_EXPIRED_NEEDLE)
+}
+
+/// Some backends signal an expired session by redirecting straight to a login
+/// page instead of a `401`/`403` or a "session expired" body.
+/// `fetch_following_redirects` already transparently follows that redirect,
+/// so without this check it would look like an ordinary `200` for whatever
+/// page the login form lives on -- invisible to the reauth trigger below.
+/// Heuristic, not exact: matches a final URL path that looks like a login
+/// page, but only when we actually got there via a redirect (a browser
+/// navigating to `/login` directly is not a session expiry).
+const LOGIN_PATH_HINTS: &[&str] = &["login", "signin", "sign-in"];
+
+fn looks_like_login_redirect(result: &FetchResult) -> bool {
+    result.redirected
+        && LOGIN_PATH_HINTS
+            .iter()
+            .any(|hint| result.final_url.path().to_ascii_lowercase().contains(hint))
+}
+
 pub fn run_proxy(
     request: &IncomingHttpRequest,
     web2_url: &str,
-    cookie: &str,
+    jar: &mut CookieJar,
     package_path: &str,
+    network_log: &mut NetworkLog,
+    mut on_auth_failure: impl FnMut(&mut CookieJar) -> anyhow::Result<bool>,
 ) -> anyhow::Result<()> {
     let blob = get_blob().unwrap();
-    let body = blob.bytes().to_vec();
+    let browser_url = request.url()?;
 
-    let request_url = request.url()?;
-
-    let url = replace_domain(
-        &request_url,
+    let base_url = replace_domain(
+        &browser_url,
         format!("{}/{}", web2_url, package_path).as_str(),
     )?;
+    let method = request.method()?;
+    let body = blob.bytes().to_vec();
 
     let mut og_headers = request
         .headers()
         .iter()
         .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
         .collect::<HashMap<String, String>>();
-
     og_headers.remove("host");
+    og_headers.remove("cookie");
 
-    match send_request_await_response(request.method()?, url, Some(og_headers), 6000, body) {
-        Ok(response) => {
-            let mut resheaders = response
-                .headers()
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
-                .collect::<HashMap<String, String>>();
-
-            resheaders.insert("set-cookie".to_string(), cookie.to_string());
-            send_response(
-                response.status(),
-                Some(resheaders),
-                response.body().to_vec(),
-            );
-            return Ok(());
-        }
-        Err(e) => {
-            return Err(e.into());
+    // We decode whatever upstream sends us ourselves, so negotiate the
+    // encodings we actually know how to handle rather than forwarding
+    // whatever the browser advertised (which may include encodings, like
+    // `br`, that `decode_body` doesn't understand).
+    let browser_accept_encoding = og_headers.remove("accept-encoding");
+    og_headers.insert("accept-encoding".to_string(), "gzip, deflate".to_string());
+
+    let mut result = fetch_following_redirects(
+        &base_url,
+        method.clone(),
+        body.clone(),
+        &og_headers,
+        jar,
+        network_log,
+    )?;
+    let mut resheaders = result.headers.clone();
+    let mut response_body = decode_body(&mut resheaders, result.body.clone())?;
+
+    // A 401/403 (or a 200 carrying a "session expired" error body) likely
+    // means our session expired server-side. Re-authenticate (subject to the
+    // caller's single-flight guard) and replay the original request exactly
+    // once with the fresh cookie before giving up. The body check runs on
+    // the already-decoded bytes, since chunk1-4 means the backend may well
+    // have sent this error body gzipped.
+    let session_expired = is_auth_failure(result.status)
+        || body_signals_session_expired(&response_body)
+        || looks_like_login_redirect(&result);
+    if session_expired && on_auth_failure(jar)? {
+        result = fetch_following_redirects(&base_url, method, body, &og_headers, jar, network_log)?;
+        resheaders = result.headers.clone();
+        response_body = decode_body(&mut resheaders, result.body.clone())?;
+    }
+
+    if let Some(loc) = &result.location {
+        if let Ok(loc_url) = result.final_url.join(loc) {
+            if let Ok(local) = rewrite_location_for_browser(&loc_url, &browser_url) {
+                resheaders.insert("location".to_string(), local.to_string());
+            }
         }
     }
+
+    let response_body =
+        encode_body_for_browser(&mut resheaders, response_body, browser_accept_encoding.as_deref())?;
+    send_response(result.status, Some(resheaders), response_body);
+    Ok(())
+}
+
+/// Issue `method body` against `base_url`, following redirects via
+/// [`follow_redirects`] until we land on a non-redirect response or a
+/// redirect with no `Location`. Injects jar cookies and absorbs `Set-Cookie`
+/// on every hop, and logs every hop to `network_log`.
+fn fetch_following_redirects(
+    base_url: &Url,
+    method: Method,
+    body: Vec<u8>,
+    og_headers: &HashMap<String, String>,
+    jar: &mut CookieJar,
+    network_log: &mut NetworkLog,
+) -> anyhow::Result<FetchResult> {
+    let (response, final_url) = follow_redirects(base_url, method, body, |method, url, body| {
+        let now = get_now();
+        jar.purge_expired(now);
+        let mut headers = og_headers.clone();
+        if let Some(cookie_header) = jar.cookie_header_for(url, now) {
+            headers.insert("cookie".to_string(), cookie_header);
+        } else {
+            headers.remove("cookie");
+        }
+
+        let started_at = now_ms();
+        let raw = send_request_await_response(
+            method.clone(),
+            url.clone(),
+            Some(headers.clone()),
+            6000,
+            body.to_vec(),
+        )
+        .map_err(anyhow::Error::from)?;
+        let duration_ms = now_ms().saturating_sub(started_at);
+
+        network_log.push(Exchange {
+            method: method.as_str().to_string(),
+            url: url.to_string(),
+            request_headers: redact_headers(&headers),
+            status: raw.status().as_u16(),
+            response_headers: redact_headers(
+                &raw.headers()
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                    .collect(),
+            ),
+            request_bytes: body.len(),
+            response_bytes: raw.body().len(),
+            duration_ms,
+            timestamp: get_now(),
+        });
+
+        // Absorb whatever upstream actually set instead of clobbering it
+        // with a single cached string, so multiple/rotating cookies and
+        // their own Domain/Path/Expires are preserved for later requests.
+        jar.absorb_set_cookie_headers(
+            raw.headers()
+                .get_all("set-cookie")
+                .iter()
+                .filter_map(|v| v.to_str().ok()),
+            url,
+        );
+
+        Ok(HopResponse {
+            status: raw.status(),
+            headers: raw
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                .collect(),
+            body: raw.body().to_vec(),
+        })
+    })?;
+
+    let location = find_header(&response.headers, "location").map(str::to_string);
+    let mut resheaders = response
+        .headers
+        .into_iter()
+        .collect::<HashMap<String, String>>();
+    resheaders.remove("set-cookie");
+
+    Ok(FetchResult {
+        status: response.status,
+        headers: resheaders,
+        body: response.body,
+        location,
+        redirected: final_url.as_str() != base_url.as_str(),
+        final_url,
+    })
 }
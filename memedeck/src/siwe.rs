@@ -0,0 +1,121 @@
+//! A structured, replay-resistant sign-in statement modeled on
+//! Sign-In-With-Ethereum (EIP-4361), used in place of the old ad-hoc
+//! `LoginMessage{site, time, nonce}` blob. Binding the signature to a
+//! domain, a fresh nonce, and an explicit validity window closes off the
+//! replay vector a fixed nonce constant left open.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long a sign-in statement remains valid for, once issued.
+const SESSION_LIFETIME_SECS: u64 = 60 * 60; // 1 hour
+
+pub struct SignInMessage {
+    pub domain: String,
+    pub node: String,
+    pub uri: String,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expiration_time: u64,
+    pub not_before: u64,
+    pub statement: Option<String>,
+}
+
+impl SignInMessage {
+    pub fn new(domain: &str, node: &str, uri: &str) -> Self {
+        let issued_at = now_secs();
+        Self {
+            domain: domain.to_string(),
+            node: node.to_string(),
+            uri: uri.to_string(),
+            nonce: fresh_nonce(node, issued_at),
+            issued_at,
+            expiration_time: issued_at + SESSION_LIFETIME_SECS,
+            not_before: issued_at,
+            statement: Some("Sign in to access your memedeck account.".to_string()),
+        }
+    }
+
+    /// Render the canonical multi-line statement that actually gets signed.
+    /// The backend re-derives this same string to verify the signature, so
+    /// the format must stay byte-for-byte stable.
+    pub fn to_canonical_string(&self) -> String {
+        let mut lines = vec![
+            format!("{} wants you to sign in with your Hyperware node:", self.domain),
+            self.node.clone(),
+            String::new(),
+        ];
+        if let Some(statement) = &self.statement {
+            lines.push(statement.clone());
+            lines.push(String::new());
+        }
+        lines.push(format!("URI: {}", self.uri));
+        lines.push("Version: 1".to_string());
+        lines.push(format!("Nonce: {}", self.nonce));
+        lines.push(format!("Issued At: {}", to_rfc3339(self.issued_at)));
+        lines.push(format!("Expiration Time: {}", to_rfc3339(self.expiration_time)));
+        lines.push(format!("Not Before: {}", to_rfc3339(self.not_before)));
+        lines.join("\n")
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// A fresh, session-scoped nonce. Combines wall-clock time (nanosecond
+/// resolution) with a process-local monotonic counter and the node id, so
+/// two logins issued in the same instant still get distinct nonces without
+/// pulling in an RNG crate this wasm target may not have entropy for.
+fn fresh_nonce(node: &str, issued_at: u64) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos();
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    node.hash(&mut hasher);
+    issued_at.hash(&mut hasher);
+    nanos.hash(&mut hasher);
+    count.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Minimal Unix-timestamp -> RFC 3339 (`YYYY-MM-DDTHH:MM:SSZ`) formatter;
+/// avoids pulling in a full date/time crate for one field.
+fn to_rfc3339(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let secs_of_day = timestamp % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Inverse of the days-from-civil algorithm used for cookie `Expires`
+/// parsing: Unix day count -> (year, month, day).
+fn civil_from_days(z: i64) -> (i64, u64, u64) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64; // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // [0, 399]
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let d = doy - (153 * mp + 2) / 5 + 1; // [1, 31]
+    let m = if mp < 10 { mp + 3 } else { mp - 9 }; // [1, 12]
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
@@ -0,0 +1,119 @@
+//! Bidirectional WebSocket proxying between the browser-facing Hyperware WS
+//! path and the web2 backend's own WebSocket endpoint. Mirrors `proxy.rs`'s
+//! job for plain HTTP, just kept stateful across the life of a connection
+//! instead of a single request/response.
+
+use std::collections::HashMap;
+
+use hyperware_process_lib::http::client::{close_ws_connection, open_ws_connection, send_ws_client_push};
+use hyperware_process_lib::http::server::{HttpServer, WsMessageType};
+use url::Url;
+
+use crate::cookie::CookieJar;
+use crate::get_now;
+
+/// How often to ping idle upstream sockets so a quiet web2 connection isn't
+/// reaped by an intermediate proxy or load balancer.
+pub const KEEPALIVE_INTERVAL_SECS: u64 = 30;
+
+struct Connection {
+    upstream_channel: u32,
+    last_activity: u64,
+}
+
+/// Tracks the live mapping between a Hyperware-side `channel_id` (what the
+/// browser's WS client is talking on) and the upstream connection opened
+/// against the web2 backend on its behalf.
+#[derive(Default)]
+pub struct WsProxy {
+    by_browser_channel: HashMap<u32, Connection>,
+    upstream_to_browser: HashMap<u32, u32>,
+}
+
+impl WsProxy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a `WebSocketOpen` from the browser: dial the same path on the
+    /// web2 origin, carrying the jar's cookies in the upgrade handshake.
+    pub fn open(&mut self, channel_id: u32, web2_ws_url: &Url, jar: &CookieJar) -> anyhow::Result<()> {
+        let mut headers = HashMap::new();
+        if let Some(cookie) = jar.cookie_header_for(web2_ws_url, get_now()) {
+            headers.insert("cookie".to_string(), cookie);
+        }
+        let upstream_channel = open_ws_connection(web2_ws_url.clone(), Some(headers), 10)?;
+        self.by_browser_channel.insert(
+            channel_id,
+            Connection {
+                upstream_channel,
+                last_activity: get_now(),
+            },
+        );
+        self.upstream_to_browser.insert(upstream_channel, channel_id);
+        Ok(())
+    }
+
+    /// Forward a frame the browser sent on `channel_id` to its upstream
+    /// counterpart, preserving text/binary framing.
+    pub fn forward_to_upstream(
+        &mut self,
+        channel_id: u32,
+        message_type: WsMessageType,
+        blob: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let Some(conn) = self.by_browser_channel.get_mut(&channel_id) else {
+            return Ok(()); // browser sent a frame on an already-closed/unknown channel
+        };
+        conn.last_activity = get_now();
+        send_ws_client_push(conn.upstream_channel, message_type, blob)?;
+        Ok(())
+    }
+
+    /// Forward a frame that arrived from the upstream connection back down
+    /// to the matching browser channel.
+    pub fn forward_to_browser(
+        &mut self,
+        http_server: &mut HttpServer,
+        upstream_channel: u32,
+        message_type: WsMessageType,
+        blob: Vec<u8>,
+    ) {
+        let Some(&channel_id) = self.upstream_to_browser.get(&upstream_channel) else {
+            return;
+        };
+        if let Some(conn) = self.by_browser_channel.get_mut(&channel_id) {
+            conn.last_activity = get_now();
+        }
+        http_server.send_ws_push(channel_id, message_type, blob);
+    }
+
+    /// Tear down both sides when the browser closes its channel.
+    pub fn close_from_browser(&mut self, channel_id: u32) {
+        if let Some(conn) = self.by_browser_channel.remove(&channel_id) {
+            self.upstream_to_browser.remove(&conn.upstream_channel);
+            let _ = close_ws_connection(conn.upstream_channel);
+        }
+    }
+
+    /// Tear down both sides when the upstream connection drops first.
+    pub fn close_from_upstream(&mut self, http_server: &mut HttpServer, upstream_channel: u32) {
+        if let Some(channel_id) = self.upstream_to_browser.remove(&upstream_channel) {
+            self.by_browser_channel.remove(&channel_id);
+            http_server.close_ws_connection(channel_id);
+        }
+    }
+
+    /// Ping every upstream connection that's been idle past
+    /// [`KEEPALIVE_INTERVAL_SECS`] so it doesn't get reaped.
+    pub fn send_keepalives(&mut self) {
+        let now = get_now();
+        for conn in self.by_browser_channel.values_mut() {
+            if now.saturating_sub(conn.last_activity) >= KEEPALIVE_INTERVAL_SECS {
+                if send_ws_client_push(conn.upstream_channel, WsMessageType::Ping, Vec::new()).is_ok() {
+                    conn.last_activity = now;
+                }
+            }
+        }
+    }
+}